@@ -0,0 +1,127 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UnixStream};
+use tokio::sync::Mutex;
+
+const CAPACITY: usize = 500;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+#[derive(Clone, Serialize)]
+pub struct Event {
+    pub timestamp: u64,
+    pub client_id: Option<usize>,
+    pub entity: Option<String>,
+    pub role: Option<String>,
+    pub kind: String,
+    pub detail: String,
+}
+
+impl Event {
+    pub fn new(kind: &str, detail: impl Into<String>) -> Self {
+        Self {
+            timestamp: unix_now(),
+            client_id: None,
+            entity: None,
+            role: None,
+            kind: kind.to_string(),
+            detail: detail.into(),
+        }
+    }
+
+    pub fn client(mut self, id: usize) -> Self {
+        self.client_id = Some(id);
+        self
+    }
+
+    pub fn entity(mut self, name: impl Into<String>, role: impl std::fmt::Debug) -> Self {
+        self.entity = Some(name.into());
+        self.role = Some(format!("{role:?}"));
+        self
+    }
+}
+
+enum Forwarder {
+    Tcp(TcpStream),
+    Unix(UnixStream),
+}
+
+impl Forwarder {
+    async fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        match self {
+            Forwarder::Tcp(stream) => {
+                stream.write_all(line.as_bytes()).await?;
+                stream.write_all(b"\n").await
+            }
+            Forwarder::Unix(stream) => {
+                stream.write_all(line.as_bytes()).await?;
+                stream.write_all(b"\n").await
+            }
+        }
+    }
+}
+
+/// An in-memory ring buffer of admin-relevant events, with an optional
+/// live forwarder for an external collector.
+pub struct LogStore {
+    events: Mutex<VecDeque<Event>>,
+    forward: Mutex<Option<Forwarder>>,
+}
+
+impl LogStore {
+    pub fn new() -> Self {
+        Self {
+            events: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+            forward: Mutex::new(None),
+        }
+    }
+
+    /// Connects a forwarder. `unix:<path>` dials a UNIX socket, anything
+    /// else is treated as a TCP address.
+    pub async fn connect_forwarder(&self, addr: &str) -> std::io::Result<()> {
+        let forwarder = match addr.strip_prefix("unix:") {
+            Some(path) => Forwarder::Unix(UnixStream::connect(path).await?),
+            None => Forwarder::Tcp(TcpStream::connect(addr).await?),
+        };
+        *self.forward.lock().await = Some(forwarder);
+        Ok(())
+    }
+
+    pub async fn record(&self, event: Event) {
+        {
+            let mut events = self.events.lock().await;
+            if events.len() == CAPACITY {
+                events.pop_front();
+            }
+            events.push_back(event.clone());
+        }
+
+        let mut forward = self.forward.lock().await;
+        if let Some(forwarder) = forward.as_mut() {
+            let Ok(line) = serde_json::to_string(&event) else {
+                return;
+            };
+            if forwarder.write_line(&line).await.is_err() {
+                *forward = None;
+            }
+        }
+    }
+
+    pub async fn tail(&self, n: usize) -> Vec<Event> {
+        self.events.lock().await.iter().rev().take(n).rev().cloned().collect()
+    }
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+
+use argon2::{Argon2, PasswordHash, PasswordVerifier};
+use russh::keys::PublicKey;
+use russh::keys::ssh_key::public::KeyData;
+
+/// One entity per non-empty, non-comment line:
+/// `name role public-key-openssh [argon2-password-hash]`.
+const COMMENT_PREFIX: &str = "#";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to read authfile")]
+    Io(#[from] std::io::Error),
+    #[error("malformed entry on line {0}")]
+    MalformedLine(usize),
+    #[error("unknown role {0:?} on line {1}")]
+    UnknownRole(String, usize),
+    #[error("invalid public key on line {0}")]
+    InvalidKey(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Admin,
+    Guest,
+}
+
+impl Role {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "admin" => Some(Role::Admin),
+            "guest" => Some(Role::Guest),
+            _ => None,
+        }
+    }
+}
+
+pub struct Entity {
+    name: String,
+    role: Role,
+    key_data: KeyData,
+    password_hash: Option<String>,
+}
+
+impl Entity {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn key_data(&self) -> KeyData {
+        self.key_data.clone()
+    }
+
+    /// Whether this entity has a configured Argon2 hash, i.e. can be
+    /// authenticated by password at all (the IRC gateway falls back to
+    /// nick-only identity for entities without one).
+    pub fn has_password(&self) -> bool {
+        self.password_hash.is_some()
+    }
+
+    /// Verifies `password` against this entity's Argon2 (PHC string) hash.
+    /// Entities without a configured hash never accept a password.
+    pub fn verify_password(&self, password: &str) -> bool {
+        let Some(hash) = &self.password_hash else {
+            return false;
+        };
+        let Ok(hash) = PasswordHash::new(hash) else {
+            return false;
+        };
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    }
+}
+
+pub struct Keychain {
+    pub entities: Vec<Arc<Entity>>,
+    pub key_pool: HashSet<KeyData>,
+}
+
+pub async fn read(path: &Path) -> Result<Keychain, Error> {
+    let contents = tokio::fs::read_to_string(path).await?;
+    let mut entities = Vec::new();
+    let mut key_pool = HashSet::new();
+
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(COMMENT_PREFIX) {
+            continue;
+        }
+
+        let mut fields = line.splitn(3, char::is_whitespace);
+        let name = fields.next().ok_or(Error::MalformedLine(line_number))?;
+        let role = fields.next().ok_or(Error::MalformedLine(line_number))?;
+        let rest = fields
+            .next()
+            .ok_or(Error::MalformedLine(line_number))?
+            .trim();
+
+        let role =
+            Role::parse(role).ok_or_else(|| Error::UnknownRole(role.to_string(), line_number))?;
+
+        // The openssh key itself is `type base64`, so only treat the tail as
+        // a password hash once it actually looks like a PHC string.
+        let (key, password_hash) = match rest.rsplit_once(char::is_whitespace) {
+            Some((key, hash)) if hash.starts_with("$argon2") => (key, Some(hash.to_string())),
+            _ => (rest, None),
+        };
+
+        let public_key =
+            PublicKey::from_openssh(key).map_err(|_| Error::InvalidKey(line_number))?;
+        let key_data = public_key.key_data().clone();
+
+        key_pool.insert(key_data.clone());
+        entities.push(Arc::new(Entity {
+            name: name.to_string(),
+            role,
+            key_data,
+            password_hash,
+        }));
+    }
+
+    Ok(Keychain { entities, key_pool })
+}
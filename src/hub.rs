@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use authfile::Entity;
+use tokio::sync::Notify;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{App, Atomic, new_atomic, storage};
+
+struct IrcMember {
+    sender: UnboundedSender<String>,
+    room: String,
+}
+
+/// Room/history state factored out of `AppServer` so the SSH/ratatui
+/// front-end and the IRC gateway operate on the same rooms.
+#[derive(Clone)]
+pub struct ChatHub {
+    app: Atomic<App>,
+    storage: storage::Storage,
+    keychain: Atomic<Vec<Arc<Entity>>>,
+    /// Fires whenever a message, join or part changes room state, so the SSH
+    /// side can redraw even when the change came from an IRC client.
+    pub render_notify: Arc<Notify>,
+    irc_members: Atomic<HashMap<String, IrcMember>>,
+    next_irc_id: Arc<AtomicUsize>,
+}
+
+impl ChatHub {
+    pub fn new(app: Atomic<App>, storage: storage::Storage, keychain: Atomic<Vec<Arc<Entity>>>) -> Self {
+        Self {
+            app,
+            storage,
+            keychain,
+            render_notify: Arc::new(Notify::new()),
+            irc_members: new_atomic(HashMap::new()),
+            // Kept well clear of the SSH side's per-connection `id` counter
+            // so the two protocols never collide in `Room::members`.
+            next_irc_id: Arc::new(AtomicUsize::new(1_000_000)),
+        }
+    }
+
+    pub fn next_irc_id(&self) -> usize {
+        self.next_irc_id.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Hands back the shared `App` state so the SSH front-end can lock it
+    /// once and render every connected client's room in a single pass.
+    pub fn app(&self) -> Atomic<App> {
+        self.app.clone()
+    }
+
+    pub async fn room_for(&self, entity_name: &str) -> Result<Option<String>, storage::Error> {
+        self.storage.room_for(entity_name).await
+    }
+
+    pub async fn entity_by_name(&self, name: &str) -> Option<Arc<Entity>> {
+        self.keychain
+            .lock()
+            .await
+            .iter()
+            .find(|entity| entity.name() == name)
+            .cloned()
+    }
+
+    pub async fn ensure_room_loaded(&self, room: &str) -> Result<(), storage::Error> {
+        if self.app.lock().await.rooms.is_loaded(room) {
+            return Ok(());
+        }
+        let history = self.storage.recent_history(room, 50).await?;
+        self.app.lock().await.rooms.seed_history(room, history);
+        Ok(())
+    }
+
+    /// Adds `id` to `room`'s membership set without posting anything.
+    pub async fn join_silent(&self, id: usize, room: &str) {
+        self.app.lock().await.rooms.join(room, id);
+        self.render_notify.notify_waiters();
+    }
+
+    /// Removes `id` from `room`'s membership set without posting anything.
+    pub async fn leave(&self, id: usize, room: &str) {
+        self.app.lock().await.rooms.part(room, id);
+        self.render_notify.notify_waiters();
+    }
+
+    /// Posts a system line (joins, parts, role announcements) to `room`.
+    pub async fn post_system(&self, room: &str, message: String) {
+        self.app.lock().await.rooms.post(room, message);
+        self.render_notify.notify_waiters();
+    }
+
+    pub async fn announce_leave(&self, room: &str, entity_name: &str) {
+        self.post_system(room, format!("{entity_name} has left")).await;
+    }
+
+    pub async fn persist_membership(&self, entity_name: &str, room: &str) -> Result<(), storage::Error> {
+        self.storage.set_membership(entity_name, room).await
+    }
+
+    /// Joins `room` with the usual "X has joined" announcement and persists
+    /// the membership, hydrating the room's history first if needed.
+    pub async fn join(&self, entity_name: &str, id: usize, room: &str) -> Result<(), storage::Error> {
+        self.ensure_room_loaded(room).await?;
+        self.join_silent(id, room).await;
+        self.post_system(room, format!("{entity_name} has joined")).await;
+        self.persist_membership(entity_name, room).await?;
+        Ok(())
+    }
+
+    pub async fn set_topic(&self, room: &str, topic: &str) -> Result<(), storage::Error> {
+        self.app
+            .lock()
+            .await
+            .rooms
+            .set_topic(room, topic.to_string());
+        self.storage.set_topic(room, topic).await?;
+        self.render_notify.notify_waiters();
+        Ok(())
+    }
+
+    pub async fn post(&self, room: &str, author: &str, body: &str) -> Result<(), storage::Error> {
+        self.app
+            .lock()
+            .await
+            .rooms
+            .post(room, format!("[{author}]: {body}"));
+        self.storage.record_message(room, author, body).await?;
+        self.broadcast_irc(room, author, body).await;
+        self.render_notify.notify_waiters();
+        Ok(())
+    }
+
+    /// Registers `nick` as an active IRC member. Returns `false` without
+    /// registering if the nick is already claimed by another connection, so
+    /// callers don't let a second client silently steal the first's sender.
+    pub async fn register_irc_member(
+        &self,
+        nick: String,
+        room: String,
+        sender: UnboundedSender<String>,
+    ) -> bool {
+        let mut members = self.irc_members.lock().await;
+        if members.contains_key(&nick) {
+            return false;
+        }
+        members.insert(nick, IrcMember { sender, room });
+        true
+    }
+
+    pub async fn move_irc_member(&self, nick: &str, room: String) {
+        if let Some(member) = self.irc_members.lock().await.get_mut(nick) {
+            member.room = room;
+        }
+    }
+
+    pub async fn unregister_irc_member(&self, nick: &str) {
+        self.irc_members.lock().await.remove(nick);
+    }
+
+    async fn broadcast_irc(&self, room: &str, author: &str, body: &str) {
+        let line = format!(":{author} PRIVMSG {room} :{body}\r\n");
+        for (nick, member) in self.irc_members.lock().await.iter() {
+            if nick == author || member.room != room {
+                continue;
+            }
+            let _ = member.sender.send(line.clone());
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+
+use crate::hub::ChatHub;
+use crate::room;
+
+/// Speaks just enough of the IRC line protocol to let ordinary IRC clients
+/// join the same rooms as the SSH/ratatui front-end.
+pub async fn listen(hub: ChatHub, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("irc gateway listening on {addr}");
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let hub = hub.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(hub, stream).await {
+                tracing::warn!("irc connection error: {e}");
+            }
+        });
+    }
+}
+
+async fn handle_connection(hub: ChatHub, stream: TcpStream) -> std::io::Result<()> {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut lines = BufReader::new(read_half).lines();
+
+    let (sender, mut receiver) = unbounded_channel::<String>();
+    tokio::spawn(async move {
+        while let Some(line) = receiver.recv().await {
+            if write_half.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut nick: Option<String> = None;
+    let mut user_seen = false;
+    let mut password: Option<String> = None;
+    let mut registered = false;
+    let mut room = room::DEFAULT_ROOM.to_string();
+    let mut id = None;
+
+    while let Some(line) = lines.next_line().await? {
+        let line = line.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        let (command, rest) = match line.split_once(' ') {
+            Some((command, rest)) => (command.to_ascii_uppercase(), rest),
+            None => (line.to_ascii_uppercase(), ""),
+        };
+
+        match command.as_str() {
+            "NICK" => nick = Some(rest.trim().to_string()),
+            "USER" => user_seen = true,
+            "PASS" => password = Some(rest.trim().to_string()),
+            "QUIT" => break,
+            _ if !registered => {
+                // Ignore everything else until registration below completes.
+            }
+            "JOIN" => {
+                if let Some(target) = rest.split_whitespace().next() {
+                    let target = room::normalize(target);
+                    let entity_name = nick.as_deref().unwrap();
+                    hub.leave(id.unwrap(), &room).await;
+                    hub.join(entity_name, id.unwrap(), &target)
+                        .await
+                        .map_err(other_io_error)?;
+                    hub.move_irc_member(entity_name, target.clone()).await;
+                    room = target;
+                }
+            }
+            "PART" => {
+                if room != room::DEFAULT_ROOM {
+                    let entity_name = nick.as_deref().unwrap();
+                    hub.leave(id.unwrap(), &room).await;
+                    hub.announce_leave(&room, entity_name).await;
+                    hub.join_silent(id.unwrap(), room::DEFAULT_ROOM).await;
+                    hub.persist_membership(entity_name, room::DEFAULT_ROOM)
+                        .await
+                        .map_err(other_io_error)?;
+                    hub.move_irc_member(entity_name, room::DEFAULT_ROOM.to_string())
+                        .await;
+                    room = room::DEFAULT_ROOM.to_string();
+                }
+            }
+            "PRIVMSG" => {
+                if let Some((target, body)) = rest.split_once(" :") {
+                    let entity_name = nick.as_deref().unwrap();
+                    hub.post(&room::normalize(target), entity_name, body)
+                        .await
+                        .map_err(other_io_error)?;
+                }
+            }
+            "TOPIC" => {
+                if let Some((target, topic)) = rest.split_once(" :") {
+                    hub.set_topic(&room::normalize(target), topic)
+                        .await
+                        .map_err(other_io_error)?;
+                }
+            }
+            _ => {}
+        }
+
+        if !registered {
+            // Wait for NICK and USER; entities with a configured Argon2
+            // hash also need a verified PASS, but entities without one
+            // (e.g. publickey-only users) authenticate by nick alone, same
+            // as before password support existed.
+            if let (Some(nick_name), true) = (nick.clone(), user_seen) {
+                if let Some(entity) = hub.entity_by_name(&nick_name).await {
+                    let authenticated = if entity.has_password() {
+                        match password.as_deref() {
+                            Some(pass) if entity.verify_password(pass) => true,
+                            Some(_) => {
+                                let _ = sender.send(format!(
+                                    ":publik 464 * {nick_name} :invalid nick/password\r\n"
+                                ));
+                                return Ok(());
+                            }
+                            None => false, // still waiting on PASS
+                        }
+                    } else {
+                        true
+                    };
+
+                    if authenticated {
+                        match complete_registration(&hub, &nick_name, &room, &sender).await? {
+                            Some(new_id) => {
+                                registered = true;
+                                id = Some(new_id);
+                            }
+                            None => return Ok(()),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if let (Some(nick_name), Some(id)) = (nick, id) {
+        hub.leave(id, &room).await;
+        hub.announce_leave(&room, &nick_name).await;
+        hub.unregister_irc_member(&nick_name).await;
+    }
+    Ok(())
+}
+
+/// Claims `nick_name` in the hub and joins `room`, rejecting with a
+/// "nickname in use" numeric if another connection already holds it.
+/// Returns the freshly minted IRC id on success.
+async fn complete_registration(
+    hub: &ChatHub,
+    nick_name: &str,
+    room: &str,
+    sender: &UnboundedSender<String>,
+) -> std::io::Result<Option<usize>> {
+    let claimed = hub
+        .register_irc_member(nick_name.to_string(), room.to_string(), sender.clone())
+        .await;
+    if !claimed {
+        let _ = sender.send(format!(
+            ":publik 433 * {nick_name} :nickname already in use\r\n"
+        ));
+        return Ok(None);
+    }
+    let id = hub.next_irc_id();
+    hub.join(nick_name, id, room).await.map_err(other_io_error)?;
+    let _ = sender.send(format!(
+        ":publik 001 {nick_name} :Welcome to publik, {nick_name}\r\n"
+    ));
+    Ok(Some(id))
+}
+
+fn other_io_error(error: impl std::error::Error) -> std::io::Error {
+    std::io::Error::other(error.to_string())
+}
@@ -16,7 +16,18 @@ use russh::{Channel, ChannelId, Pty};
 use tokio::sync::Mutex;
 use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
 use tui_textarea::TextArea;
+mod audit;
 mod authfile;
+mod hub;
+mod irc;
+mod recorder;
+mod room;
+mod storage;
+mod telemetry;
+
+use hub::ChatHub;
+use recorder::Recorder;
+use storage::Storage;
 
 type SshTerminal = Terminal<CrosstermBackend<TerminalHandle>>;
 
@@ -30,7 +41,7 @@ type Atomic<T> = Arc<Mutex<T>>;
 
 #[derive(Default)]
 struct App {
-    pub history: Vec<String>,
+    pub rooms: room::RoomRegistry,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -41,6 +52,10 @@ pub enum Error {
     Russh(#[from] russh::Error),
     #[error("failed to read authorization file")]
     Authfile(#[from] authfile::Error),
+    #[error("failed to write terminal recording")]
+    Recording(#[source] std::io::Error),
+    #[error("storage error")]
+    Storage(#[from] storage::Error),
 }
 
 pub struct Client {
@@ -48,6 +63,14 @@ pub struct Client {
     handle: Handle,
     terminal: SshTerminal,
     textarea: TextArea<'static>,
+    recorder: Atomic<Recorder>,
+    room: String,
+    log_overlay: bool,
+    /// The input box's title absent any transient `status_message`.
+    title: String,
+    /// A one-shot notice shown only to this client on the input box's
+    /// title, e.g. a permission denial. Cleared the next time it renders.
+    status_message: Option<String>,
     // entity: Arc<Entity>,
 }
 
@@ -58,13 +81,14 @@ struct TerminalHandle {
 }
 
 impl TerminalHandle {
-    async fn start(handle: Handle, channel_id: ChannelId) -> Self {
+    async fn start(handle: Handle, channel_id: ChannelId, recorder: Atomic<Recorder>) -> Self {
         let (sender, mut receiver) = unbounded_channel::<Vec<u8>>();
         tokio::spawn(async move {
             while let Some(data) = receiver.recv().await {
+                recorder.lock().await.output(&data).await.ok();
                 let result = handle.data(channel_id, data.into()).await;
                 if result.is_err() {
-                    eprintln!("Failed to send data: {:?}", result);
+                    tracing::warn!(?result, "failed to send data");
                 }
             }
         });
@@ -102,17 +126,111 @@ struct AppServer {
     key_data_pool: Atomic<HashSet<KeyData>>,
     key_data_to_user: Atomic<HashMap<KeyData, Arc<Entity>>>,
     key_data_to_id: Atomic<HashMap<KeyData, Vec<usize>>>,
+    /// Mirrors `key_data_to_id` for password-authenticated sessions, which
+    /// have no `KeyData` of their own, so `reload` can evict them too.
+    name_to_id: Atomic<HashMap<String, Vec<usize>>>,
     id_to_user: Atomic<HashMap<usize, Arc<Entity>>>,
     clients: Atomic<HashMap<usize, Client>>,
 
     id: usize,
+    hub: ChatHub,
+    log_store: Arc<audit::LogStore>,
+    metrics: Arc<telemetry::Metrics>,
+}
+
+/// Redraws every connected client's pane from the room it is currently in.
+/// Shared between `AppServer::render` (SSH-triggered) and the hub's
+/// render-notify task (triggered by activity from the IRC gateway).
+async fn redraw(
+    clients: Atomic<HashMap<usize, Client>>,
     app: Atomic<App>,
+    log_store: Arc<audit::LogStore>,
+) {
+    let app = app.lock().await;
+    for (
+        _,
+        Client {
+            terminal,
+            textarea,
+            room,
+            log_overlay,
+            title,
+            status_message,
+            ..
+        },
+    ) in clients.lock().await.iter_mut()
+    {
+        if *log_overlay {
+            let events = log_store.tail(40).await;
+            terminal
+                .draw(|f| {
+                    let area = f.area();
+                    f.render_widget(Clear, area);
+                    let lines: Vec<_> = events
+                        .iter()
+                        .map(|event| {
+                            Text::raw(format!(
+                                "[{}] {} {} {}",
+                                event.timestamp,
+                                event.kind,
+                                event.entity.as_deref().unwrap_or("-"),
+                                event.detail
+                            ))
+                        })
+                        .collect();
+                    let list = List::new(lines)
+                        .block(Block::bordered().title("Audit log (Ctrl+L to close)"));
+                    f.render_widget(list, area);
+                })
+                .unwrap();
+            continue;
+        }
+
+        let history: Vec<String> = app
+            .rooms
+            .history(room)
+            .iter()
+            .rev()
+            .take(20)
+            .rev()
+            .cloned()
+            .collect();
+        let block_title = match status_message.take() {
+            Some(message) => format!("{title} - {message}"),
+            None => title.clone(),
+        };
+        textarea.set_block(Block::bordered().title(block_title));
+
+        terminal
+            .draw(|f| {
+                // clear the screen
+                let area = f.area();
+                f.render_widget(Clear, area);
+
+                let layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(vec![Constraint::Fill(1), Constraint::Length(4)])
+                    .split(f.area());
+                let style = Style::default().fg(Color::Green);
+
+                let paragraphs: Vec<_> = history
+                    .iter()
+                    .map(|message| Text::styled(message.to_string(), style))
+                    .collect();
+
+                let paragraphs = List::new(paragraphs);
+                f.render_widget(paragraphs, layout[0]);
+                f.render_widget(&*textarea, layout[1]);
+            })
+            .unwrap();
+    }
 }
 
 impl AppServer {
     pub async fn run(&mut self) -> Result<(), anyhow::Error> {
         let mut methods = russh::MethodSet::empty();
         methods.push(russh::MethodKind::PublicKey);
+        methods.push(russh::MethodKind::Password);
 
         let config = Config {
             inactivity_timeout: Some(std::time::Duration::from_secs(3600)),
@@ -139,8 +257,14 @@ impl AppServer {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self), fields(client_id = self.id))]
     async fn reload(&mut self) -> Result<(), Error> {
         let new_keychain = authfile::read(Path::new("./authfile")).await?;
+        let new_names: HashSet<String> = new_keychain
+            .entities
+            .iter()
+            .map(|e| e.name().to_string())
+            .collect();
 
         // freeze all maps in the server state
         {
@@ -148,10 +272,11 @@ impl AppServer {
             let mut key_data_pool = self.key_data_pool.lock().await;
             let mut key_data_to_id = self.key_data_to_id.lock().await;
             let mut key_data_to_user = self.key_data_to_user.lock().await;
+            let mut name_to_id = self.name_to_id.lock().await;
             let mut clients = self.clients.lock().await;
             let mut id_to_user = self.id_to_user.lock().await;
 
-            // find all strays
+            // find all publickey-authenticated strays
             for stray in key_data_pool.difference(&new_keychain.key_pool) {
                 let Some(ids) = key_data_to_id.get(stray) else {
                     continue;
@@ -159,16 +284,70 @@ impl AppServer {
 
                 // these IDs are now invalid
                 for id in ids.iter() {
-                    let client = &clients[id];
+                    let Some(client) = clients.get(id) else {
+                        continue;
+                    };
                     if let Err(()) = client.handle.close(client.channel).await {
                         return Err(Error::ClientDisconnectFailed(*id));
                     }
-                    clients.remove(id);
+                    if let Some(entity) = id_to_user.get(id) {
+                        self.log_store
+                            .record(
+                                audit::Event::new(
+                                    "stray_disconnect",
+                                    "stray key disconnected during reload",
+                                )
+                                .client(*id)
+                                .entity(entity.name().to_string(), entity.role()),
+                            )
+                            .await;
+                    }
+                    if clients.remove(id).is_some() {
+                        self.metrics.client_disconnected();
+                    }
                     id_to_user.remove(id);
                 }
                 key_data_to_id.remove(stray);
             }
 
+            // find all password-authenticated strays; these have no KeyData
+            // of their own, so they're tracked by entity name instead
+            let stray_names: Vec<String> = name_to_id
+                .keys()
+                .filter(|name| !new_names.contains(*name))
+                .cloned()
+                .collect();
+            for name in stray_names {
+                let Some(ids) = name_to_id.remove(&name) else {
+                    continue;
+                };
+
+                for id in ids.iter() {
+                    let Some(client) = clients.get(id) else {
+                        continue;
+                    };
+                    if let Err(()) = client.handle.close(client.channel).await {
+                        return Err(Error::ClientDisconnectFailed(*id));
+                    }
+                    if let Some(entity) = id_to_user.get(id) {
+                        self.log_store
+                            .record(
+                                audit::Event::new(
+                                    "stray_disconnect",
+                                    "stray password credential disconnected during reload",
+                                )
+                                .client(*id)
+                                .entity(entity.name().to_string(), entity.role()),
+                            )
+                            .await;
+                    }
+                    if clients.remove(id).is_some() {
+                        self.metrics.client_disconnected();
+                    }
+                    id_to_user.remove(id);
+                }
+            }
+
             *key_data_to_user = new_keychain
                 .entities
                 .iter()
@@ -177,7 +356,10 @@ impl AppServer {
             *keychain = new_keychain.entities;
             *key_data_pool = new_keychain.key_pool;
         }
-        log::info!("authfile synchronized to memory");
+        self.log_store
+            .record(audit::Event::new("reload", "authfile reloaded from disk"))
+            .await;
+        tracing::info!("authfile synchronized to memory");
         Ok(())
     }
 
@@ -185,60 +367,97 @@ impl AppServer {
         self.id_to_user.lock().await[&self.id].clone()
     }
 
+    async fn current_room(&mut self) -> String {
+        self.clients.lock().await[&self.id].room.clone()
+    }
+
+    async fn join_room(&mut self, name: &str) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Ok(());
+        }
+        let name = room::normalize(name);
+        if self.current_room().await == name {
+            return Ok(());
+        }
+        let entity_name = self.entity().await.name().to_string();
+        let previous = {
+            let mut clients = self.clients.lock().await;
+            let client = clients.get_mut(&self.id).unwrap();
+            std::mem::replace(&mut client.room, name.clone())
+        };
+        self.hub.leave(self.id, &previous).await;
+        self.hub.join(&entity_name, self.id, &name).await?;
+        let role = self.entity().await.role();
+        self.log_store
+            .record(
+                audit::Event::new("room_join", format!("joined {name}"))
+                    .client(self.id)
+                    .entity(entity_name, role),
+            )
+            .await;
+        Ok(())
+    }
+
+    async fn part_room(&mut self) -> Result<(), Error> {
+        let room = self.current_room().await;
+        if room == room::DEFAULT_ROOM {
+            return Ok(());
+        }
+        let entity_name = self.entity().await.name().to_string();
+        {
+            let mut clients = self.clients.lock().await;
+            clients.get_mut(&self.id).unwrap().room = room::DEFAULT_ROOM.to_string();
+        }
+        self.hub.leave(self.id, &room).await;
+        self.hub.announce_leave(&room, &entity_name).await;
+        self.hub.join_silent(self.id, room::DEFAULT_ROOM).await;
+        self.hub
+            .persist_membership(&entity_name, room::DEFAULT_ROOM)
+            .await?;
+        Ok(())
+    }
+
+    async fn set_topic(&mut self, topic: String) -> Result<(), Error> {
+        let room = self.current_room().await;
+        self.hub.set_topic(&room, &topic).await?;
+        Ok(())
+    }
+
     async fn announce(&mut self) {
         let entity = self.entity().await;
-        self.app.lock().await.history.push(format!(
-            "{} with {:?} privileges has joined",
-            entity.name(),
-            entity.role()
-        ));
+        let room = self.current_room().await;
+        self.hub
+            .post_system(
+                &room,
+                format!(
+                    "{} with {:?} privileges has joined",
+                    entity.name(),
+                    entity.role()
+                ),
+            )
+            .await;
     }
 
     async fn render(&mut self) {
-        let clients = self.clients.clone();
-        let history: Vec<String> = self
-            .app
-            .lock()
-            .await
-            .history
-            .iter()
-            .rev()
-            .take(20)
-            .rev()
-            .cloned()
-            .collect();
-        tokio::spawn(async move {
-            for (
-                _,
-                Client {
-                    terminal, textarea, ..
-                },
-            ) in clients.lock().await.iter_mut()
-            {
-                terminal
-                    .draw(|f| {
-                        // clear the screen
-                        let area = f.area();
-                        f.render_widget(Clear, area);
-
-                        let layout = Layout::default()
-                            .direction(Direction::Vertical)
-                            .constraints(vec![Constraint::Fill(1), Constraint::Length(4)])
-                            .split(f.area());
-                        let style = Style::default().fg(Color::Green);
-
-                        let paragraphs: Vec<_> = history
-                            .iter()
-                            .map(|message| Text::styled(message.to_string(), style))
-                            .collect();
-
-                        let paragraphs = List::new(paragraphs);
-                        f.render_widget(paragraphs, layout[0]);
-                        f.render_widget(&*textarea, layout[1]);
-                    })
-                    .unwrap();
-            }
-        });
+        tokio::spawn(redraw(
+            self.clients.clone(),
+            self.hub.app(),
+            self.log_store.clone(),
+        ));
+    }
+
+    async fn toggle_log_overlay(&mut self) -> Result<(), Error> {
+        let entity = self.entity().await;
+        let mut clients = self.clients.lock().await;
+        let client = clients.get_mut(&self.id).unwrap();
+        if entity.role() != authfile::Role::Admin {
+            // Shown only on this client's own statusline, not broadcast to
+            // the room or persisted to the audit log.
+            client.status_message = Some("not authorized to view the audit log".to_string());
+            return Ok(());
+        }
+        client.log_overlay = !client.log_overlay;
+        Ok(())
     }
 }
 
@@ -250,23 +469,34 @@ impl Server for AppServer {
         s
     }
     fn handle_session_error(&mut self, _error: <Self::Handler as russh::server::Handler>::Error) {
-        eprintln!("Session error: {:#?}", _error);
+        tracing::error!(client_id = self.id, error = ?_error, "session error");
     }
 }
 
 impl Handler for AppServer {
     type Error = Error;
 
+    #[tracing::instrument(skip(self, channel, session), fields(client_id = self.id, entity = tracing::field::Empty))]
     async fn channel_open_session(
         &mut self,
         channel: Channel<Msg>,
         session: &mut Session,
     ) -> Result<bool, Self::Error> {
+        let entity_name = self.entity().await.name().to_string();
+        tracing::Span::current().record("entity", entity_name.as_str());
+        let room = self
+            .hub
+            .room_for(&entity_name)
+            .await?
+            .unwrap_or_else(|| room::DEFAULT_ROOM.to_string());
+        self.hub.ensure_room_loaded(&room).await?;
+
         {
-            // let entity = self.entity().await;
             let channel = channel.id();
             let handle = session.handle();
-            let terminal_handle = TerminalHandle::start(handle.clone(), channel.clone()).await;
+            let recorder = new_atomic(Recorder::create(self.id).await.map_err(Error::Recording)?);
+            let terminal_handle =
+                TerminalHandle::start(handle.clone(), channel.clone(), recorder.clone()).await;
 
             let backend = CrosstermBackend::new(terminal_handle);
 
@@ -287,7 +517,7 @@ impl Handler for AppServer {
             };
 
             let mut textarea = TextArea::default();
-            textarea.set_block(Block::bordered().title(title));
+            textarea.set_block(Block::bordered().title(title.clone()));
 
             let mut clients = self.clients.lock().await;
             clients.insert(
@@ -297,13 +527,30 @@ impl Handler for AppServer {
                     channel,
                     handle,
                     terminal,
+                    recorder,
+                    room: room.clone(),
+                    log_overlay: false,
+                    title,
+                    status_message: None,
                 },
             );
         }
+        self.hub.join_silent(self.id, &room).await;
+        self.hub.persist_membership(&entity_name, &room).await?;
+        let role = self.entity().await.role();
+        self.log_store
+            .record(
+                audit::Event::new("connect", "client connected")
+                    .client(self.id)
+                    .entity(entity_name, role),
+            )
+            .await;
+        self.metrics.client_connected();
         self.announce().await;
         Ok(true)
     }
 
+    #[tracing::instrument(skip(self, key), fields(client_id = self.id, entity = tracing::field::Empty))]
     async fn auth_publickey(&mut self, _: &str, key: &PublicKey) -> Result<Auth, Self::Error> {
         // Search for the key in our keychain
         if let Some(entity) = self.key_data_to_user.lock().await.get(key.key_data()) {
@@ -318,11 +565,66 @@ impl Handler for AppServer {
                 .or_default()
                 .push(self.id);
 
+            tracing::Span::current().record("entity", entity.name());
+            self.log_store
+                .record(
+                    audit::Event::new("auth_accept", "public key accepted")
+                        .client(self.id)
+                        .entity(entity.name().to_string(), entity.role()),
+                )
+                .await;
+            self.metrics.auth_accepted();
+
             return Ok(Auth::Accept);
         }
+        self.log_store
+            .record(audit::Event::new("auth_reject", "public key rejected").client(self.id))
+            .await;
+        self.metrics.auth_rejected();
         Ok(Auth::reject())
     }
 
+    #[tracing::instrument(skip(self, password), fields(client_id = self.id, entity = user))]
+    async fn auth_password(&mut self, user: &str, password: &str) -> Result<Auth, Self::Error> {
+        let entity = self
+            .keychain
+            .lock()
+            .await
+            .iter()
+            .find(|entity| entity.name() == user)
+            .cloned();
+
+        if let Some(entity) = entity {
+            if entity.verify_password(password) {
+                self.id_to_user.lock().await.insert(self.id, entity.clone());
+                self.name_to_id
+                    .lock()
+                    .await
+                    .entry(entity.name().to_string())
+                    .or_default()
+                    .push(self.id);
+
+                self.log_store
+                    .record(
+                        audit::Event::new("auth_accept", "password accepted")
+                            .client(self.id)
+                            .entity(entity.name().to_string(), entity.role()),
+                    )
+                    .await;
+                self.metrics.auth_accepted();
+
+                return Ok(Auth::Accept);
+            }
+        }
+
+        self.log_store
+            .record(audit::Event::new("auth_reject", "password rejected").client(self.id))
+            .await;
+        self.metrics.auth_rejected();
+        Ok(Auth::reject())
+    }
+
+    #[tracing::instrument(skip(self, _channel, data, _session), fields(client_id = self.id))]
     async fn data(
         &mut self,
         _channel: ChannelId,
@@ -351,9 +653,17 @@ impl Handler for AppServer {
                     ));
                 text
             };
-            let name = self.entity().await.name().to_string();
-            let message = format!("[{name}]: {text}");
-            self.app.lock().await.history.push(message);
+            if let Some(room_name) = text.strip_prefix("/join ") {
+                self.join_room(room_name).await?;
+            } else if text.trim() == "/part" {
+                self.part_room().await?;
+            } else if let Some(topic) = text.strip_prefix("/topic ") {
+                self.set_topic(topic.trim().to_string()).await?;
+            } else {
+                let name = self.entity().await.name().to_string();
+                let room = self.current_room().await;
+                self.hub.post(&room, &name, &text).await?;
+            }
         }
 
         if !data.is_empty() {
@@ -363,6 +673,10 @@ impl Handler for AppServer {
                 Ok(Event::Key(Key::Ctrl('r'))) => {
                     self.check_role_and_reload().await?;
                 }
+                // Press `Ctrl-l` to toggle the admin-only audit log overlay
+                Ok(Event::Key(Key::Ctrl('l'))) => {
+                    self.toggle_log_overlay().await?;
+                }
                 Ok(keycode) => {
                     self.clients
                         .lock()
@@ -373,7 +687,7 @@ impl Handler for AppServer {
                         .input(keycode);
                 }
                 Err(e) => {
-                    log::warn!("failed to parse keyboard input data: {:?}: {e}", data);
+                    tracing::warn!(?data, error = %e, "failed to parse keyboard input data");
                 }
             }
         }
@@ -382,6 +696,7 @@ impl Handler for AppServer {
         Ok(())
     }
 
+    #[tracing::instrument(skip(self, channel, session), fields(client_id = self.id))]
     async fn pty_request(
         &mut self,
         channel: ChannelId,
@@ -404,6 +719,13 @@ impl Handler for AppServer {
             let mut clients = self.clients.lock().await;
             let client = clients.get_mut(&self.id).unwrap();
             client.terminal.resize(rect).unwrap();
+            client
+                .recorder
+                .lock()
+                .await
+                .header(col_width as u16, row_height as u16)
+                .await
+                .map_err(Error::Recording)?;
 
             session.channel_success(channel)?;
         }
@@ -417,22 +739,35 @@ impl Drop for AppServer {
     fn drop(&mut self) {
         let id = self.id;
         let clients = self.clients.clone();
+        let id_to_user = self.id_to_user.clone();
+        let log_store = self.log_store.clone();
+        let metrics = self.metrics.clone();
         tokio::spawn(async move {
-            let mut clients = clients.lock().await;
-            clients.remove(&id);
+            let had_client = clients.lock().await.remove(&id).is_some();
+            if let Some(entity) = id_to_user.lock().await.get(&id) {
+                log_store
+                    .record(
+                        audit::Event::new("disconnect", "client disconnected")
+                            .client(id)
+                            .entity(entity.name().to_string(), entity.role()),
+                    )
+                    .await;
+            }
+            if had_client {
+                metrics.client_disconnected();
+            }
         });
     }
 }
 
 #[tokio::main]
 async fn main() {
-    env_logger::builder()
-        .filter_level(log::LevelFilter::Debug)
-        .init();
+    let metrics = telemetry::init();
 
     let keychain = authfile::read(Path::new("./authfile")).await.unwrap();
     let key_data_pool = new_atomic(keychain.key_pool);
     let key_data_to_id = new_atomic(HashMap::new());
+    let name_to_id = new_atomic(HashMap::new());
     let id_to_user = new_atomic(HashMap::new());
     let clients = new_atomic(HashMap::new());
     let key_data_to_user = new_atomic(
@@ -444,15 +779,57 @@ async fn main() {
     );
     let keychain = new_atomic(keychain.entities);
 
+    let storage = Storage::open("./publik.sqlite3").await.unwrap();
+    let mut app = App::default();
+    for (room, topic) in storage.topics().await.unwrap() {
+        app.rooms.set_topic(&room, topic);
+    }
+    let app = new_atomic(app);
+
+    let hub = ChatHub::new(app.clone(), storage, keychain.clone());
+
+    let log_store = Arc::new(audit::LogStore::new());
+    if let Ok(addr) = std::env::var("PUBLIK_AUDIT_FORWARD") {
+        if let Err(e) = log_store.connect_forwarder(&addr).await {
+            tracing::warn!("failed to connect audit forwarder at {addr}: {e}");
+        }
+    }
+
+    // Redraw every SSH client whenever the IRC gateway changes room state.
+    {
+        let clients = clients.clone();
+        let app = app.clone();
+        let log_store = log_store.clone();
+        let notify = hub.render_notify.clone();
+        tokio::spawn(async move {
+            loop {
+                notify.notified().await;
+                redraw(clients.clone(), app.clone(), log_store.clone()).await;
+            }
+        });
+    }
+
+    {
+        let hub = hub.clone();
+        tokio::spawn(async move {
+            if let Err(e) = irc::listen(hub, "0.0.0.0:6667").await {
+                tracing::error!("irc gateway failed: {e}");
+            }
+        });
+    }
+
     let mut sh = AppServer {
-        app: new_atomic(App::default()),
         keychain,
         id_to_user,
         key_data_to_id,
+        name_to_id,
         key_data_pool,
         key_data_to_user,
         clients,
         id: 0,
+        hub,
+        log_store,
+        metrics,
     };
     sh.run().await.unwrap();
 }
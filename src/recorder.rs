@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tokio::fs::{self, File};
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+const RECORDINGS_DIR: &str = "./recordings";
+
+#[derive(Serialize)]
+struct Header {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: u64,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// A viewport size assumed when output needs recording before `pty_request`
+/// has reported the client's real dimensions.
+const DEFAULT_WIDTH: u16 = 80;
+const DEFAULT_HEIGHT: u16 = 24;
+
+/// Captures a client's terminal output as an asciicast v2 (.cast) recording.
+pub struct Recorder {
+    writer: Option<BufWriter<File>>,
+    start: Instant,
+    header_written: bool,
+}
+
+impl Recorder {
+    /// Opens `./recordings/<id>-<unix timestamp>.cast` for append-only writes.
+    pub async fn create(id: usize) -> std::io::Result<Self> {
+        fs::create_dir_all(RECORDINGS_DIR).await?;
+        let path = PathBuf::from(RECORDINGS_DIR).join(format!("{id}-{}.cast", unix_now()));
+        let file = File::create(path).await?;
+        Ok(Self {
+            writer: Some(BufWriter::new(file)),
+            start: Instant::now(),
+            header_written: false,
+        })
+    }
+
+    async fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let Some(writer) = self.writer.as_mut() else {
+            return Ok(());
+        };
+        writer.write_all(line.as_bytes()).await?;
+        writer.write_all(b"\n").await
+    }
+
+    /// Writes the asciicast header if it hasn't been written yet, so it is
+    /// always the cast's first line regardless of whether `pty_request` or
+    /// an early `output` call reaches the recorder first.
+    async fn ensure_header(&mut self, width: u16, height: u16) -> std::io::Result<()> {
+        if self.header_written {
+            return Ok(());
+        }
+        let header = Header {
+            version: 2,
+            width,
+            height,
+            timestamp: unix_now(),
+        };
+        let line = serde_json::to_string(&header)?;
+        self.write_line(&line).await?;
+        self.header_written = true;
+        Ok(())
+    }
+
+    /// Emits the asciicast header. Called once `pty_request` knows the
+    /// viewport size; a no-op if output already forced a default header.
+    pub async fn header(&mut self, width: u16, height: u16) -> std::io::Result<()> {
+        self.ensure_header(width, height).await
+    }
+
+    /// Appends an `[seconds, "o", chunk]` output event relative to recording
+    /// start. Empty chunks are skipped, and a default-sized header is
+    /// written first if `pty_request` hasn't run yet.
+    pub async fn output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+        self.ensure_header(DEFAULT_WIDTH, DEFAULT_HEIGHT).await?;
+        let elapsed = self.start.elapsed().as_secs_f64();
+        let chunk = String::from_utf8_lossy(data);
+        let line = serde_json::to_string(&(elapsed, "o", chunk))?;
+        self.write_line(&line).await
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        // BufWriter can't be flushed synchronously here, so hand the owned
+        // writer off to a detached task to drain on close, mirroring how
+        // `AppServer::drop` clones its state into a spawned cleanup task.
+        if let Some(mut writer) = self.writer.take() {
+            tokio::spawn(async move {
+                let _ = writer.flush().await;
+            });
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::{HashMap, HashSet};
+
+/// The room every client starts in and falls back to after `/part`.
+pub const DEFAULT_ROOM: &str = "#lobby";
+
+/// Normalizes a room name the same way on both front-ends, so SSH's
+/// `/join general` and IRC's `JOIN #general` land in the same room.
+pub fn normalize(name: &str) -> String {
+    let name = name.trim();
+    match name.strip_prefix('#') {
+        Some(_) => name.to_string(),
+        None => format!("#{name}"),
+    }
+}
+
+#[derive(Default)]
+pub struct Room {
+    pub history: Vec<String>,
+    pub topic: Option<String>,
+    pub members: HashSet<usize>,
+    /// Whether `history` has been hydrated from `Storage` this process run.
+    loaded: bool,
+}
+
+/// Scopes chat history, topics and membership to named rooms instead of one
+/// global broadcast.
+#[derive(Default)]
+pub struct RoomRegistry {
+    rooms: HashMap<String, Room>,
+}
+
+impl RoomRegistry {
+    pub fn join(&mut self, name: &str, id: usize) {
+        self.rooms.entry(name.to_string()).or_default().members.insert(id);
+    }
+
+    pub fn part(&mut self, name: &str, id: usize) {
+        if let Some(room) = self.rooms.get_mut(name) {
+            room.members.remove(&id);
+        }
+    }
+
+    pub fn post(&mut self, name: &str, message: String) {
+        self.rooms.entry(name.to_string()).or_default().history.push(message);
+    }
+
+    pub fn set_topic(&mut self, name: &str, topic: String) {
+        self.rooms.entry(name.to_string()).or_default().topic = Some(topic);
+    }
+
+    pub fn history(&self, name: &str) -> &[String] {
+        self.rooms
+            .get(name)
+            .map(|room| room.history.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn is_loaded(&self, name: &str) -> bool {
+        self.rooms.get(name).is_some_and(|room| room.loaded)
+    }
+
+    /// Prepends persisted history to `name`, once per process run.
+    pub fn seed_history(&mut self, name: &str, lines: Vec<String>) {
+        let room = self.rooms.entry(name.to_string()).or_default();
+        if room.loaded {
+            return;
+        }
+        room.history = lines.into_iter().chain(room.history.drain(..)).collect();
+        room.loaded = true;
+    }
+}
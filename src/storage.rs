@@ -0,0 +1,134 @@
+use chrono::Utc;
+use sqlx::Row;
+use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("sqlite error")]
+    Sqlx(#[from] sqlx::Error),
+}
+
+/// Durable backing store for chat history, room topics and memberships.
+#[derive(Clone)]
+pub struct Storage {
+    pool: SqlitePool,
+}
+
+impl Storage {
+    pub async fn open(path: &str) -> Result<Self, Error> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .connect(&format!("sqlite://{path}?mode=rwc"))
+            .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS messages (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                room TEXT NOT NULL,
+                author TEXT NOT NULL,
+                body TEXT NOT NULL,
+                timestamp TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS topics (
+                room TEXT PRIMARY KEY,
+                topic TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS memberships (
+                entity TEXT PRIMARY KEY,
+                room TEXT NOT NULL
+            )",
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+
+    /// Appends a chat line, stamped with the current UTC time.
+    pub async fn record_message(&self, room: &str, author: &str, body: &str) -> Result<(), Error> {
+        sqlx::query("INSERT INTO messages (room, author, body, timestamp) VALUES (?, ?, ?, ?)")
+            .bind(room)
+            .bind(author)
+            .bind(body)
+            .bind(Utc::now().to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the last `limit` lines for `room`, oldest first.
+    pub async fn recent_history(&self, room: &str, limit: i64) -> Result<Vec<String>, Error> {
+        let rows = sqlx::query(
+            "SELECT author, body FROM messages WHERE room = ? ORDER BY id DESC LIMIT ?",
+        )
+        .bind(room)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut history: Vec<String> = rows
+            .into_iter()
+            .map(|row| {
+                let author: String = row.get("author");
+                let body: String = row.get("body");
+                format!("[{author}]: {body}")
+            })
+            .collect();
+        history.reverse();
+        Ok(history)
+    }
+
+    pub async fn set_topic(&self, room: &str, topic: &str) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO topics (room, topic) VALUES (?, ?)
+             ON CONFLICT(room) DO UPDATE SET topic = excluded.topic",
+        )
+        .bind(room)
+        .bind(topic)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// All persisted room topics, loaded once at startup.
+    pub async fn topics(&self) -> Result<Vec<(String, String)>, Error> {
+        let rows = sqlx::query("SELECT room, topic FROM topics")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (row.get("room"), row.get("topic")))
+            .collect())
+    }
+
+    /// Records the room `entity` last joined, so reconnects land back there.
+    pub async fn set_membership(&self, entity: &str, room: &str) -> Result<(), Error> {
+        sqlx::query(
+            "INSERT INTO memberships (entity, room) VALUES (?, ?)
+             ON CONFLICT(entity) DO UPDATE SET room = excluded.room",
+        )
+        .bind(entity)
+        .bind(room)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn room_for(&self, entity: &str) -> Result<Option<String>, Error> {
+        let row = sqlx::query("SELECT room FROM memberships WHERE entity = ?")
+            .bind(entity)
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.map(|row| row.get("room")))
+    }
+}
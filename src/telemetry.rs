@@ -0,0 +1,142 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use opentelemetry::KeyValue;
+use opentelemetry::metrics::MeterProvider;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::Config as TraceConfig;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+
+fn resource() -> Resource {
+    Resource::new(vec![KeyValue::new("service.name", "publik")])
+}
+
+/// Running counters surfaced, when an OTLP endpoint is configured, as
+/// observable OTel metrics — so accepted/rejected auths and active clients
+/// are queryable in a real backend. Auth counts are also logged as `tracing`
+/// events; the active-client count is not.
+#[derive(Default)]
+pub struct Metrics {
+    active_clients: AtomicI64,
+    auth_accepted: AtomicU64,
+    auth_rejected: AtomicU64,
+}
+
+impl Metrics {
+    pub fn client_connected(&self) {
+        self.active_clients.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn client_disconnected(&self) {
+        self.active_clients.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub fn auth_accepted(&self) {
+        let total = self.auth_accepted.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!(total, "auth accepted");
+    }
+
+    pub fn auth_rejected(&self) {
+        let total = self.auth_rejected.fetch_add(1, Ordering::Relaxed) + 1;
+        tracing::info!(total, "auth rejected");
+    }
+
+    pub fn active_clients(&self) -> i64 {
+        self.active_clients.load(Ordering::Relaxed)
+    }
+}
+
+/// Registers observable instruments on the global meter that read straight
+/// from `metrics`. A no-op unless `init` installed a real OTLP meter
+/// provider, in which case these are what actually gets exported.
+fn register_instruments(metrics: Arc<Metrics>) {
+    let meter = opentelemetry::global::meter("publik");
+
+    let gauge = meter.i64_observable_gauge("publik.active_clients").init();
+    let accepted = meter.u64_observable_counter("publik.auth_accepted").init();
+    let rejected = meter.u64_observable_counter("publik.auth_rejected").init();
+
+    meter
+        .register_callback(&[gauge.as_any(), accepted.as_any(), rejected.as_any()], {
+            move |observer| {
+                observer.observe_i64(&gauge, metrics.active_clients(), &[]);
+                observer.observe_u64(
+                    &accepted,
+                    metrics.auth_accepted.load(Ordering::Relaxed),
+                    &[],
+                );
+                observer.observe_u64(
+                    &rejected,
+                    metrics.auth_rejected.load(Ordering::Relaxed),
+                    &[],
+                );
+            }
+        })
+        .expect("instrument callback registration should not fail with valid instruments");
+}
+
+/// Initializes `tracing` and OTel metrics, exporting both over OTLP when
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` is set, and always logging to stderr.
+pub fn init() -> Arc<Metrics> {
+    let metrics = Arc::new(Metrics::default());
+    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    let fmt_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stderr);
+
+    match std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT") {
+        Ok(endpoint) => {
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(&endpoint),
+                )
+                .with_trace_config(TraceConfig::default().with_resource(resource()))
+                .install_batch(opentelemetry_sdk::runtime::Tokio);
+
+            match tracer {
+                Ok(tracer) => {
+                    tracing_subscriber::registry()
+                        .with(env_filter)
+                        .with(fmt_layer)
+                        .with(tracing_opentelemetry::layer().with_tracer(tracer))
+                        .init();
+                }
+                Err(e) => {
+                    tracing_subscriber::registry()
+                        .with(env_filter)
+                        .with(fmt_layer)
+                        .init();
+                    tracing::warn!("failed to initialize OTLP trace exporter: {e}");
+                }
+            }
+
+            let meter_provider = opentelemetry_otlp::new_pipeline()
+                .metrics(opentelemetry_sdk::runtime::Tokio)
+                .with_exporter(
+                    opentelemetry_otlp::new_exporter()
+                        .tonic()
+                        .with_endpoint(&endpoint),
+                )
+                .with_resource(resource())
+                .build();
+
+            match meter_provider {
+                Ok(provider) => opentelemetry::global::set_meter_provider(provider),
+                Err(e) => tracing::warn!("failed to initialize OTLP metrics exporter: {e}"),
+            }
+        }
+        Err(_) => {
+            tracing_subscriber::registry()
+                .with(env_filter)
+                .with(fmt_layer)
+                .init();
+        }
+    }
+
+    register_instruments(metrics.clone());
+    metrics
+}